@@ -1,6 +1,6 @@
 use super::{
-    block_cache_sync_all, get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode,
-    SuperBlock,
+    get_block_cache, Bitmap, BlockDevice, DiskInode, DiskInodeType, Inode, SuperBlock,
+    DEFAULT_DIR_MODE,
 };
 use crate::BLOCK_SZ;
 use alloc::sync::Arc;
@@ -14,7 +14,30 @@ pub struct EasyFileSystem {
     ///Data bitmap
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
-    data_area_start_block: u32,
+    pub(crate) data_area_start_block: u32,
+    /// number of data blocks actually backing the data area, which is not the
+    /// same as `data_bitmap.maximum()` (the bitmap is sized in whole blocks of
+    /// bits, so its capacity can exceed the data area it is tracking)
+    data_area_blocks: u32,
+    /// cached count of currently free data blocks, kept in sync by `alloc_data`/`dealloc_data`
+    /// (and, for a `check`/`repair` reconciliation, `repair` itself)
+    pub(crate) free_data_blocks: u32,
+    /// cached count of currently free inodes, kept in sync by `alloc_inode`/`dealloc_inode`
+    free_inodes: u32,
+}
+/// Free-space and inode accounting for `statfs`-style queries, see [`EasyFileSystem::stat`]
+#[derive(Debug, Clone, Copy)]
+pub struct FsStat {
+    /// size in bytes of a single block
+    pub block_size: u32,
+    /// total number of data blocks the filesystem can allocate
+    pub total_blocks: u32,
+    /// data blocks not currently allocated to any inode
+    pub free_blocks: u32,
+    /// total number of inodes the filesystem can allocate
+    pub total_inodes: u32,
+    /// inodes not currently allocated to any file or directory
+    pub free_inodes: u32,
 }
 
 type DataBlock = [u8; BLOCK_SZ];
@@ -47,12 +70,17 @@ impl EasyFileSystem {
         );
 
         // 构建 EasyFileSystem 实例
+        let free_inodes = inode_bitmap.maximum() as u32;
+        let free_data_blocks = data_area_blocks;
         let mut efs = Self {
             block_device: Arc::clone(&block_device),  // 克隆块设备引用
             inode_bitmap,  // 设置 inode 位图
             data_bitmap,  // 设置数据位图
             inode_area_start_block: 1 + inode_bitmap_blocks,  // inode 区域起始块
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,  // 数据区域起始块
+            data_area_blocks,
+            free_inodes,
+            free_data_blocks,
         };
 
         // clear all blocks: 清空所有块的内容
@@ -91,7 +119,7 @@ impl EasyFileSystem {
         )
             .lock()  // 获取锁
             .modify(root_inode_offset, |disk_inode: &mut DiskInode| {  // 设置根 inode 的类型
-                disk_inode.initialize(DiskInodeType::Directory);  // 初始化为目录类型
+                disk_inode.initialize(DiskInodeType::Directory, DEFAULT_DIR_MODE, 0, 0, 0);  // 初始化为目录类型
             });
 
         // 返回一个 Arc<Mutex<Self>>，表示文件系统对象
@@ -106,15 +134,32 @@ impl EasyFileSystem {
                 assert!(super_block.is_valid(), "Error loading EFS!");
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+                let inode_bitmap = Bitmap::new(1, super_block.inode_bitmap_blocks as usize);
+                let data_bitmap = Bitmap::new(
+                    (1 + inode_total_blocks) as usize,
+                    super_block.data_bitmap_blocks as usize,
+                );
+                // seed the free-space counters with a one-time scan; alloc/dealloc
+                // keep them in sync from here on so later queries are O(1)
+                let free_inodes = inode_bitmap.maximum() as u32
+                    - inode_bitmap.iter_set(&block_device).len() as u32;
+                // trailing bits beyond data_area_blocks may be burned (see
+                // alloc_data) without backing a real block; don't count them
+                let used_data_blocks = data_bitmap
+                    .iter_set(&block_device)
+                    .into_iter()
+                    .filter(|&bit| (bit as u32) < super_block.data_area_blocks)
+                    .count() as u32;
+                let free_data_blocks = super_block.data_area_blocks - used_data_blocks;
                 let efs = Self {
                     block_device,
-                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
-                    data_bitmap: Bitmap::new(
-                        (1 + inode_total_blocks) as usize,
-                        super_block.data_bitmap_blocks as usize,
-                    ),
+                    inode_bitmap,
+                    data_bitmap,
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    data_area_blocks: super_block.data_area_blocks,
+                    free_inodes,
+                    free_data_blocks,
                 };
                 Arc::new(Mutex::new(efs))
             })
@@ -143,12 +188,24 @@ impl EasyFileSystem {
     }
     /// Allocate a new inode
     pub fn alloc_inode(&mut self) -> u32 {
-        self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
+        let inode_id = self.inode_bitmap.alloc(&self.block_device).unwrap() as u32;
+        self.free_inodes -= 1;
+        inode_id
     }
 
     /// Allocate a data block
     pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+        // data_bitmap is sized in whole blocks of bits, so its capacity can
+        // run a little past data_area_blocks; those trailing bits don't back
+        // a real block, so burn them (leave them marked used) instead of
+        // handing them out
+        loop {
+            let bit = self.data_bitmap.alloc(&self.block_device).unwrap() as u32;
+            if bit < self.data_area_blocks {
+                self.free_data_blocks -= 1;
+                return bit + self.data_area_start_block;
+            }
+        }
     }
     /// Deallocate a data block
     pub fn dealloc_data(&mut self, block_id: u32) {
@@ -162,6 +219,125 @@ impl EasyFileSystem {
         self.data_bitmap.dealloc(
             &self.block_device,
             (block_id - self.data_area_start_block) as usize,
-        )
+        );
+        self.free_data_blocks += 1;
+    }
+    /// Deallocate an inode, freeing every data block it owns and clearing its bit
+    /// in `inode_bitmap` so `alloc_inode` can hand the slot out again
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        let data_blocks_dealloc = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, |disk_inode: &mut DiskInode| {
+                let blocks = disk_inode.clear_size(&self.block_device);
+                // clear_size only zeroes size and the in-use block pointers; fully
+                // zero the rest (type/mode/ownership/timestamps) so a freed slot
+                // doesn't carry stale metadata until `initialize` reuses it
+                disk_inode.initialize(DiskInodeType::File, 0, 0, 0, 0);
+                blocks
+            });
+        for data_block in data_blocks_dealloc.into_iter() {
+            self.dealloc_data(data_block);
+        }
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize);
+        self.free_inodes += 1;
+    }
+    /// Total number of data blocks the filesystem can allocate
+    pub fn total_blocks(&self) -> u32 {
+        self.data_area_blocks
+    }
+    /// Data blocks not currently allocated to any inode, tracked in O(1)
+    pub fn free_blocks(&self) -> u32 {
+        self.free_data_blocks
+    }
+    /// Total number of inodes the filesystem can allocate
+    pub fn total_inodes(&self) -> u32 {
+        self.inode_bitmap.maximum() as u32
+    }
+    /// Inodes not currently allocated to any file or directory, tracked in O(1)
+    pub fn free_inodes(&self) -> u32 {
+        self.free_inodes
+    }
+    /// Bundle free-space and inode accounting for `statfs`-style queries
+    pub fn stat(&self) -> FsStat {
+        FsStat {
+            block_size: BLOCK_SZ as u32,
+            total_blocks: self.total_blocks(),
+            free_blocks: self.free_blocks(),
+            total_inodes: self.total_inodes(),
+            free_inodes: self.free_inodes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::MemBlockDevice;
+
+    /// 1 inode bitmap block needs 2048 inode-area blocks, so the device has
+    /// to be at least that big before any data area exists.
+    const TOTAL_BLOCKS: u32 = 2070;
+
+    #[test]
+    fn stat_reflects_alloc_and_dealloc_of_data_blocks_and_inodes() {
+        let _guard = crate::tests::lock();
+        let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(TOTAL_BLOCKS as usize));
+        let efs = EasyFileSystem::create(Arc::clone(&device), TOTAL_BLOCKS, 1);
+
+        let stat0 = efs.lock().stat();
+
+        let (inode_id, data_block) = {
+            let mut fs = efs.lock();
+            let inode_id = fs.alloc_inode();
+            let data_block = fs.alloc_data();
+            (inode_id, data_block)
+        };
+        let stat1 = efs.lock().stat();
+        assert_eq!(stat1.free_inodes, stat0.free_inodes - 1);
+        assert_eq!(stat1.free_blocks, stat0.free_blocks - 1);
+
+        efs.lock().dealloc_data(data_block);
+        efs.lock().dealloc_inode(inode_id);
+        let stat2 = efs.lock().stat();
+        assert_eq!(stat2.free_inodes, stat0.free_inodes);
+        assert_eq!(stat2.free_blocks, stat0.free_blocks);
+        assert_eq!(stat2.total_blocks, stat0.total_blocks);
+        assert_eq!(stat2.total_inodes, stat0.total_inodes);
+    }
+
+    #[test]
+    fn dealloc_inode_zeroes_metadata_of_the_freed_slot() {
+        let _guard = crate::tests::lock();
+        let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(TOTAL_BLOCKS as usize));
+        let efs = EasyFileSystem::create(Arc::clone(&device), TOTAL_BLOCKS, 1);
+
+        let inode_id = {
+            let mut fs = efs.lock();
+            let inode_id = fs.alloc_inode();
+            let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+            get_block_cache(block_id as usize, Arc::clone(&device))
+                .lock()
+                .modify(block_offset, |disk_inode: &mut DiskInode| {
+                    disk_inode.initialize(DiskInodeType::Directory, DEFAULT_DIR_MODE, 1, 2, 42);
+                });
+            inode_id
+        };
+
+        efs.lock().dealloc_inode(inode_id);
+
+        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&device))
+            .lock()
+            .read(block_offset, |disk_inode: &DiskInode| {
+                assert!(disk_inode.is_file());
+                assert_eq!(disk_inode.mode, 0);
+                assert_eq!(disk_inode.uid, 0);
+                assert_eq!(disk_inode.gid, 0);
+                assert_eq!(disk_inode.atime, 0);
+                assert_eq!(disk_inode.mtime, 0);
+                assert_eq!(disk_inode.ctime, 0);
+            });
     }
 }
@@ -0,0 +1,188 @@
+use super::{get_block_cache, DiskInode, EasyFileSystem, SuperBlock};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Problems that stop [`EasyFileSystem::check`] from completing a scan
+#[derive(Debug)]
+pub enum FsckError {
+    /// the image's super block failed `SuperBlock::is_valid`
+    InvalidSuperBlock,
+}
+
+/// Result of scanning a filesystem image for bitmap/inode inconsistencies
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    /// data blocks marked used in `data_bitmap` but unreachable from any inode (leaks)
+    pub leaked_data_blocks: Vec<u32>,
+    /// data blocks reachable from an inode but marked free in `data_bitmap`
+    /// (corruption — another inode may claim them next)
+    pub corrupted_data_blocks: Vec<u32>,
+    /// data blocks reachable from more than one inode at once (double-allocation)
+    pub double_allocated_blocks: Vec<u32>,
+    /// inode ids marked used in `inode_bitmap` whose `DiskInode` has neither a
+    /// valid file nor directory type
+    pub inconsistent_inodes: Vec<u32>,
+}
+
+impl FsckReport {
+    /// Whether the scan found nothing wrong
+    pub fn is_clean(&self) -> bool {
+        self.leaked_data_blocks.is_empty()
+            && self.corrupted_data_blocks.is_empty()
+            && self.double_allocated_blocks.is_empty()
+            && self.inconsistent_inodes.is_empty()
+    }
+}
+
+impl EasyFileSystem {
+    /// Scan the inode and data bitmaps against the blocks actually referenced by
+    /// allocated inodes.
+    ///
+    /// Rebuilds a reference count by walking every allocated inode's
+    /// direct/indirect/indirect2/indirect3 pointers via
+    /// `DiskInode::referenced_blocks`, then diffs it against `data_bitmap` to find
+    /// leaked and corrupted blocks, and flags any block referenced more than once
+    /// as double-allocated. Read-only: use [`EasyFileSystem::repair`] to act on
+    /// the report.
+    pub fn check(&self) -> Result<FsckReport, FsckError> {
+        let super_block_valid = get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .read(0, |super_block: &SuperBlock| super_block.is_valid());
+        if !super_block_valid {
+            return Err(FsckError::InvalidSuperBlock);
+        }
+        let mut ref_counts: BTreeMap<usize, u32> = BTreeMap::new();
+        let mut inconsistent_inodes = Vec::new();
+        for inode_id in self.inode_bitmap.iter_set(&self.block_device) {
+            let (block_id, block_offset) = self.get_disk_inode_pos(inode_id as u32);
+            let (is_consistent, blocks) =
+                get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                    .lock()
+                    .read(block_offset, |disk_inode: &DiskInode| {
+                        let is_consistent = disk_inode.is_dir() || disk_inode.is_file();
+                        (is_consistent, disk_inode.referenced_blocks(&self.block_device))
+                    });
+            if !is_consistent {
+                inconsistent_inodes.push(inode_id as u32);
+            }
+            for block_id in blocks {
+                let bit = (block_id - self.data_area_start_block) as usize;
+                *ref_counts.entry(bit).or_insert(0) += 1;
+            }
+        }
+        // trailing bits past the data area are burned by alloc_data without
+        // backing a real block, so they are neither leaks nor data blocks
+        let allocated: BTreeSet<usize> = self
+            .data_bitmap
+            .iter_set(&self.block_device)
+            .into_iter()
+            .filter(|&bit| bit < self.total_blocks() as usize)
+            .collect();
+        let referenced: BTreeSet<usize> = ref_counts.keys().copied().collect();
+        let leaked_data_blocks = allocated
+            .difference(&referenced)
+            .map(|&bit| bit as u32 + self.data_area_start_block)
+            .collect();
+        let corrupted_data_blocks = referenced
+            .difference(&allocated)
+            .map(|&bit| bit as u32 + self.data_area_start_block)
+            .collect();
+        let double_allocated_blocks = ref_counts
+            .into_iter()
+            .filter(|&(_, count)| count > 1)
+            .map(|(bit, _)| bit as u32 + self.data_area_start_block)
+            .collect();
+        Ok(FsckReport {
+            leaked_data_blocks,
+            corrupted_data_blocks,
+            double_allocated_blocks,
+            inconsistent_inodes,
+        })
+    }
+    /// Reconcile `data_bitmap` with a [`FsckReport`]: free every leaked block and
+    /// mark every corrupted (referenced-but-free) block used again
+    pub fn repair(&mut self, report: &FsckReport) {
+        for &block_id in &report.leaked_data_blocks {
+            // route through dealloc_data so free_data_blocks stays in sync
+            self.dealloc_data(block_id);
+        }
+        for &block_id in &report.corrupted_data_blocks {
+            self.data_bitmap.set_bit(
+                &self.block_device,
+                (block_id - self.data_area_start_block) as usize,
+                true,
+            );
+            self.free_data_blocks -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::MemBlockDevice;
+    use crate::BlockDevice;
+
+    /// Small enough to build quickly, large enough to leave a handful of data
+    /// blocks to play with: 1 inode bitmap block needs 2048 inode-area blocks,
+    /// so the device has to be at least that big before any data area exists.
+    const TOTAL_BLOCKS: u32 = 2070;
+
+    #[test]
+    fn check_finds_leaks_and_double_allocation_and_repair_frees_them() {
+        let _guard = crate::tests::lock();
+        let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(TOTAL_BLOCKS as usize));
+        let efs = EasyFileSystem::create(Arc::clone(&device), TOTAL_BLOCKS, 1);
+
+        let (leaked, dup) = {
+            let mut fs = efs.lock();
+            let leaked = fs.alloc_data();
+            let dup = fs.alloc_data();
+            let (block_id, block_offset) = fs.get_disk_inode_pos(0);
+            get_block_cache(block_id as usize, Arc::clone(&device))
+                .lock()
+                .modify(block_offset, |disk_inode: &mut DiskInode| {
+                    disk_inode.increase_size(2 * crate::BLOCK_SZ as u32, alloc::vec![dup, dup], &device);
+                });
+            (leaked, dup)
+        };
+
+        let free_before_repair = efs.lock().free_blocks();
+        let report = efs.lock().check().expect("super block is valid");
+        assert_eq!(report.leaked_data_blocks, alloc::vec![leaked]);
+        assert_eq!(report.double_allocated_blocks, alloc::vec![dup]);
+        assert!(report.corrupted_data_blocks.is_empty());
+        assert!(!report.is_clean());
+
+        efs.lock().repair(&report);
+        // repair only frees the leak; it leaves the double-allocation flagged
+        // but unresolved, since collapsing it would mean picking which
+        // reference wins -- that's outside what a bitmap-level repair can do
+        let (free_after_repair, still_allocated) = {
+            let fs = efs.lock();
+            let allocated: Vec<u32> = fs
+                .data_bitmap
+                .iter_set(&device)
+                .into_iter()
+                .map(|bit| bit as u32 + fs.data_area_start_block)
+                .collect();
+            (fs.free_blocks(), allocated)
+        };
+        assert_eq!(free_after_repair, free_before_repair + 1);
+        assert!(!still_allocated.contains(&leaked));
+    }
+
+    #[test]
+    fn check_rejects_an_invalid_super_block() {
+        let _guard = crate::tests::lock();
+        let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(TOTAL_BLOCKS as usize));
+        let efs = EasyFileSystem::create(Arc::clone(&device), TOTAL_BLOCKS, 1);
+        get_block_cache(0, Arc::clone(&device))
+            .lock()
+            .modify(0, |bytes: &mut [u8; crate::BLOCK_SZ]| {
+                bytes.iter_mut().for_each(|b| *b = 0);
+            });
+        assert!(matches!(efs.lock().check(), Err(FsckError::InvalidSuperBlock)));
+    }
+}
@@ -0,0 +1,744 @@
+use super::{get_block_cache, BlockDevice, BLOCK_SZ};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+const EFS_MAGIC: u32 = 0x3b800001;
+/// The number of direct inodes
+const INODE_DIRECT_COUNT: usize = 28;
+/// The max length of inode name
+const NAME_LENGTH_LIMIT: usize = 27;
+/// The number of indirect1 inodes
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
+/// The number of indirect2 inodes
+const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
+/// The upper bound of direct inode index
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+/// The upper bound of indirect1 inode index
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+/// The upper bound of indirect2 inode index
+const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+/// The number of indirect3 inodes
+const INODE_INDIRECT3_COUNT: usize = INODE_INDIRECT2_COUNT * INODE_INDIRECT1_COUNT;
+/// The upper bound of indirect3 inode index
+#[allow(unused)]
+const INDIRECT3_BOUND: usize = INDIRECT2_BOUND + INODE_INDIRECT3_COUNT;
+
+/// Super block of a filesystem
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl core::fmt::Debug for SuperBlock {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("SuperBlock")
+            .field("total_blocks", &self.total_blocks)
+            .field("inode_bitmap_blocks", &self.inode_bitmap_blocks)
+            .field("inode_area_blocks", &self.inode_area_blocks)
+            .field("data_bitmap_blocks", &self.data_bitmap_blocks)
+            .field("data_area_blocks", &self.data_area_blocks)
+            .finish()
+    }
+}
+
+impl SuperBlock {
+    /// Initialize a super block
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+    /// Check if the super block is valid using the magic number
+    pub fn is_valid(&self) -> bool {
+        self.magic == EFS_MAGIC
+    }
+}
+/// Type of a disk inode
+#[repr(u32)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DiskInodeType {
+    File,
+    Directory,
+}
+
+/// Owner-readable bit, see [`DiskInode::mode`]
+pub const S_IRUSR: u32 = 0o400;
+/// Owner-writable bit, see [`DiskInode::mode`]
+pub const S_IWUSR: u32 = 0o200;
+/// Owner-executable bit, see [`DiskInode::mode`]
+pub const S_IXUSR: u32 = 0o100;
+/// Default mode for a newly created file: rw-r--r--
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+/// Default mode for a newly created directory: rwxr-xr-x
+pub const DEFAULT_DIR_MODE: u32 = 0o755;
+
+/// A indirect block
+type IndirectBlock = [u32; BLOCK_SZ / 4];
+/// A data block
+type DataBlock = [u8; BLOCK_SZ];
+/// A disk inode
+///
+/// Every field is a `u32` and the trailing `_reserved` array pads the struct
+/// out to exactly 256 bytes, a power-of-two divisor of `BLOCK_SZ`, so
+/// `get_disk_inode_pos`'s `inodes_per_block` division stays exact.
+#[repr(C)]
+pub struct DiskInode {
+    pub size: u32,
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    pub indirect1: u32,
+    pub indirect2: u32,
+    pub indirect3: u32,
+    type_: DiskInodeType,
+    /// rwx bits for owner/group/other, see the `S_I*USR` constants
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// seconds since the epoch of the last access
+    pub atime: u32,
+    /// seconds since the epoch of the last data modification
+    pub mtime: u32,
+    /// seconds since the epoch of the last metadata change
+    pub ctime: u32,
+    _reserved: [u32; 25],
+}
+
+impl DiskInode {
+    /// Initialize a disk inode, as well as all direct inodes under it
+    /// indirect1/indirect2/indirect3 blocks are allocated only when they are needed
+    pub fn initialize(&mut self, type_: DiskInodeType, mode: u32, uid: u32, gid: u32, time: u32) {
+        self.size = 0;
+        self.direct.iter_mut().for_each(|v| *v = 0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.indirect3 = 0;
+        self.type_ = type_;
+        self.mode = mode;
+        self.uid = uid;
+        self.gid = gid;
+        self.atime = time;
+        self.mtime = time;
+        self.ctime = time;
+        self._reserved = [0; 25];
+    }
+    /// Update owner/permission metadata; bumps `ctime` since the inode changed
+    pub fn set_permissions(&mut self, mode: u32, uid: u32, gid: u32, time: u32) {
+        self.mode = mode;
+        self.uid = uid;
+        self.gid = gid;
+        self.ctime = time;
+    }
+    /// Record that the inode's contents were accessed/modified at `time`
+    pub fn touch(&mut self, time: u32) {
+        self.atime = time;
+        self.mtime = time;
+        self.ctime = time;
+    }
+    /// Whether this inode is a directory
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+    /// Whether this inode is a file
+    #[allow(unused)]
+    pub fn is_file(&self) -> bool {
+        self.type_ == DiskInodeType::File
+    }
+    /// Return block number correspond to size
+    pub fn data_blocks(&self) -> u32 {
+        Self::_data_blocks(self.size)
+    }
+    fn _data_blocks(size: u32) -> u32 {
+        size.div_ceil(BLOCK_SZ as u32)
+    }
+    /// Return number of blocks needed include indirect1/2/3
+    pub fn total_blocks(size: u32) -> u32 {
+        let data_blocks = Self::_data_blocks(size) as usize;
+        let mut total = data_blocks;
+        // indirect1
+        if data_blocks > INODE_DIRECT_COUNT {
+            total += 1;
+        }
+        // indirect2
+        if data_blocks > INDIRECT1_BOUND {
+            total += 1;
+            // sub indirect1, bounded to the indirect2 region -- data blocks past
+            // INDIRECT2_BOUND live under indirect3 and are counted below instead
+            total +=
+                (data_blocks.min(INDIRECT2_BOUND) - INDIRECT1_BOUND).div_ceil(INODE_INDIRECT1_COUNT);
+        }
+        // indirect3
+        if data_blocks > INDIRECT2_BOUND {
+            total += 1;
+            let rest = data_blocks - INDIRECT2_BOUND;
+            // sub indirect2 blocks
+            total += rest.div_ceil(INODE_INDIRECT2_COUNT);
+            // sub indirect1 blocks hanging off those indirect2 blocks
+            total += rest.div_ceil(INODE_INDIRECT1_COUNT);
+        }
+        total as u32
+    }
+    /// Get the number of data blocks that have to be allocated given the new size of data
+    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+    /// Get id of block given inner id
+    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < INODE_DIRECT_COUNT {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect_block: &IndirectBlock| {
+                    indirect_block[inner_id - INODE_DIRECT_COUNT]
+                })
+        } else if inner_id < INDIRECT2_BOUND {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            get_block_cache(indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect_block: &IndirectBlock| {
+                    indirect_block[last % INODE_INDIRECT1_COUNT]
+                })
+        } else {
+            let last = inner_id - INDIRECT2_BOUND;
+            let indirect2 = get_block_cache(self.indirect3 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect3: &IndirectBlock| {
+                    indirect3[last / INODE_INDIRECT2_COUNT]
+                });
+            let rest = last % INODE_INDIRECT2_COUNT;
+            let indirect1 = get_block_cache(indirect2 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[rest / INODE_INDIRECT1_COUNT]
+                });
+            get_block_cache(indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect_block: &IndirectBlock| {
+                    indirect_block[rest % INODE_INDIRECT1_COUNT]
+                })
+        }
+    }
+    /// Increase the size of the current disk inode
+    pub fn increase_size(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        let mut current_blocks = self.data_blocks();
+        self.size = new_size;
+        let mut total_blocks = self.data_blocks();
+        let mut new_blocks = new_blocks.into_iter();
+        // fill direct
+        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
+            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
+            current_blocks += 1;
+        }
+        // alloc indirect1
+        if total_blocks > INODE_DIRECT_COUNT as u32 {
+            if current_blocks == INODE_DIRECT_COUNT as u32 {
+                self.indirect1 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_DIRECT_COUNT as u32;
+            total_blocks -= INODE_DIRECT_COUNT as u32;
+        } else {
+            return;
+        }
+        // fill indirect1
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
+                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
+                    current_blocks += 1;
+                }
+            });
+        // alloc indirect2
+        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
+            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
+                self.indirect2 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_INDIRECT1_COUNT as u32;
+            total_blocks -= INODE_INDIRECT1_COUNT as u32;
+        } else {
+            return;
+        }
+        // fill indirect2 from (a0, b0) to (a1, b1), bounded to this level's own
+        // capacity -- data past INODE_INDIRECT2_COUNT lives under indirect3 and
+        // is handled by the section below instead
+        let indirect2_total = total_blocks.min(INODE_INDIRECT2_COUNT as u32);
+        let mut a0 = current_blocks.min(INODE_INDIRECT2_COUNT as u32) as usize / INODE_INDIRECT1_COUNT;
+        let mut b0 = current_blocks.min(INODE_INDIRECT2_COUNT as u32) as usize % INODE_INDIRECT1_COUNT;
+        let a1 = indirect2_total as usize / INODE_INDIRECT1_COUNT;
+        let b1 = indirect2_total as usize % INODE_INDIRECT1_COUNT;
+        // alloc low-level indirect1
+        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                while (a0 < a1) || (a0 == a1 && b0 < b1) {
+                    if b0 == 0 {
+                        indirect2[a0] = new_blocks.next().unwrap();
+                    }
+                    // fill current
+                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            indirect1[b0] = new_blocks.next().unwrap();
+                        });
+                    // move to next
+                    b0 += 1;
+                    if b0 == INODE_INDIRECT1_COUNT {
+                        b0 = 0;
+                        a0 += 1;
+                    }
+                }
+            });
+        // the (a0, b0) walk above only tracks local progress within this
+        // level's index block, so bring current_blocks back in sync with how
+        // far the indirect2 region is actually filled before checking whether
+        // indirect3 needs to be allocated
+        current_blocks = current_blocks.max(indirect2_total);
+        // alloc indirect3
+        if total_blocks > INODE_INDIRECT2_COUNT as u32 {
+            if current_blocks == INODE_INDIRECT2_COUNT as u32 {
+                self.indirect3 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_INDIRECT2_COUNT as u32;
+            total_blocks -= INODE_INDIRECT2_COUNT as u32;
+        } else {
+            return;
+        }
+        // fill indirect3 from (c0, a0, b0) to (c1, a1, b1)
+        let mut c0 = current_blocks as usize / INODE_INDIRECT2_COUNT;
+        let mut a0 = (current_blocks as usize % INODE_INDIRECT2_COUNT) / INODE_INDIRECT1_COUNT;
+        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
+        let c1 = total_blocks as usize / INODE_INDIRECT2_COUNT;
+        let a1 = (total_blocks as usize % INODE_INDIRECT2_COUNT) / INODE_INDIRECT1_COUNT;
+        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect3 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect3: &mut IndirectBlock| {
+                while (c0 < c1) || (c0 == c1 && ((a0 < a1) || (a0 == a1 && b0 < b1))) {
+                    if a0 == 0 && b0 == 0 {
+                        indirect3[c0] = new_blocks.next().unwrap();
+                    }
+                    get_block_cache(indirect3[c0] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect2: &mut IndirectBlock| {
+                            if b0 == 0 {
+                                indirect2[a0] = new_blocks.next().unwrap();
+                            }
+                            get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
+                                .lock()
+                                .modify(0, |indirect1: &mut IndirectBlock| {
+                                    indirect1[b0] = new_blocks.next().unwrap();
+                                });
+                        });
+                    // move to next
+                    b0 += 1;
+                    if b0 == INODE_INDIRECT1_COUNT {
+                        b0 = 0;
+                        a0 += 1;
+                        if a0 == INODE_INDIRECT1_COUNT {
+                            a0 = 0;
+                            c0 += 1;
+                        }
+                    }
+                }
+            });
+    }
+    /// Clear size to zero and return blocks that should be deallocated
+    ///
+    /// We will clear the block contents to zero later
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut v: Vec<u32> = Vec::new();
+        let mut data_blocks = self.data_blocks() as usize;
+        self.size = 0;
+        let mut current_blocks = 0usize;
+        // direct
+        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
+            v.push(self.direct[current_blocks]);
+            self.direct[current_blocks] = 0;
+            current_blocks += 1;
+        }
+        // indirect1 block
+        if data_blocks > INODE_DIRECT_COUNT {
+            v.push(self.indirect1);
+            data_blocks -= INODE_DIRECT_COUNT;
+            current_blocks = 0;
+        } else {
+            return v;
+        }
+        // indirect1
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
+                    v.push(indirect1[current_blocks]);
+                    current_blocks += 1;
+                }
+            });
+        self.indirect1 = 0;
+        // indirect2 block
+        if data_blocks > INODE_INDIRECT1_COUNT {
+            v.push(self.indirect2);
+            data_blocks -= INODE_INDIRECT1_COUNT;
+        } else {
+            return v;
+        }
+        // indirect3 block
+        if data_blocks > INODE_INDIRECT2_COUNT {
+            v.push(self.indirect3);
+            data_blocks -= INODE_INDIRECT2_COUNT;
+        } else {
+            Self::dealloc_indirect2(self.indirect2, data_blocks, block_device, &mut v);
+            self.indirect2 = 0;
+            return v;
+        }
+        Self::dealloc_indirect2(self.indirect2, INODE_INDIRECT2_COUNT, block_device, &mut v);
+        self.indirect2 = 0;
+        // indirect3
+        assert!(data_blocks <= INODE_INDIRECT3_COUNT);
+        let c1 = data_blocks / INODE_INDIRECT2_COUNT;
+        let rest = data_blocks % INODE_INDIRECT2_COUNT;
+        get_block_cache(self.indirect3 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect3: &mut IndirectBlock| {
+                // full indirect2 blocks
+                for entry in indirect3.iter_mut().take(c1) {
+                    v.push(*entry);
+                    Self::dealloc_indirect2(*entry, INODE_INDIRECT2_COUNT, block_device, &mut v);
+                }
+                // last, partially-filled indirect2 block
+                if rest > 0 {
+                    v.push(indirect3[c1]);
+                    Self::dealloc_indirect2(indirect3[c1], rest, block_device, &mut v);
+                }
+            });
+        self.indirect3 = 0;
+        v
+    }
+    /// Deallocate `count` data blocks reachable through an indirect2 block,
+    /// pushing the freed indirect1 blocks and data blocks into `v`
+    fn dealloc_indirect2(
+        indirect2_block_id: u32,
+        count: usize,
+        block_device: &Arc<dyn BlockDevice>,
+        v: &mut Vec<u32>,
+    ) {
+        let a1 = count / INODE_INDIRECT1_COUNT;
+        let b1 = count % INODE_INDIRECT1_COUNT;
+        get_block_cache(indirect2_block_id as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                // full indirect1 blocks
+                for entry in indirect2.iter_mut().take(a1) {
+                    v.push(*entry);
+                    get_block_cache(*entry as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            for entry in indirect1.iter() {
+                                v.push(*entry);
+                            }
+                        });
+                }
+                // last indirect1 block
+                if b1 > 0 {
+                    v.push(indirect2[a1]);
+                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            for entry in indirect1.iter().take(b1) {
+                                v.push(*entry);
+                            }
+                        });
+                }
+            });
+    }
+    /// Collect every block id this inode owns — its direct/indirect1/indirect2/indirect3
+    /// data blocks as well as the metadata blocks used to address them — without
+    /// mutating anything. Used by `EasyFileSystem::check` to rebuild a reference
+    /// bitmap for the filesystem consistency checker.
+    pub fn referenced_blocks(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut v: Vec<u32> = Vec::new();
+        let mut data_blocks = self.data_blocks() as usize;
+        let mut current_blocks = 0usize;
+        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
+            v.push(self.direct[current_blocks]);
+            current_blocks += 1;
+        }
+        if data_blocks > INODE_DIRECT_COUNT {
+            v.push(self.indirect1);
+            data_blocks -= INODE_DIRECT_COUNT;
+            current_blocks = 0;
+        } else {
+            return v;
+        }
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect1: &IndirectBlock| {
+                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
+                    v.push(indirect1[current_blocks]);
+                    current_blocks += 1;
+                }
+            });
+        if data_blocks > INODE_INDIRECT1_COUNT {
+            v.push(self.indirect2);
+            data_blocks -= INODE_INDIRECT1_COUNT;
+        } else {
+            return v;
+        }
+        if data_blocks > INODE_INDIRECT2_COUNT {
+            v.push(self.indirect3);
+            data_blocks -= INODE_INDIRECT2_COUNT;
+        } else {
+            Self::collect_indirect2(self.indirect2, data_blocks, block_device, &mut v);
+            return v;
+        }
+        Self::collect_indirect2(self.indirect2, INODE_INDIRECT2_COUNT, block_device, &mut v);
+        assert!(data_blocks <= INODE_INDIRECT3_COUNT);
+        let c1 = data_blocks / INODE_INDIRECT2_COUNT;
+        let rest = data_blocks % INODE_INDIRECT2_COUNT;
+        get_block_cache(self.indirect3 as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect3: &IndirectBlock| {
+                for entry in indirect3.iter().take(c1) {
+                    v.push(*entry);
+                    Self::collect_indirect2(*entry, INODE_INDIRECT2_COUNT, block_device, &mut v);
+                }
+                if rest > 0 {
+                    v.push(indirect3[c1]);
+                    Self::collect_indirect2(indirect3[c1], rest, block_device, &mut v);
+                }
+            });
+        v
+    }
+    /// Read-only counterpart of `dealloc_indirect2`: collect the data and indirect1
+    /// block ids reachable through an indirect2 block without modifying them
+    fn collect_indirect2(
+        indirect2_block_id: u32,
+        count: usize,
+        block_device: &Arc<dyn BlockDevice>,
+        v: &mut Vec<u32>,
+    ) {
+        let a1 = count / INODE_INDIRECT1_COUNT;
+        let b1 = count % INODE_INDIRECT1_COUNT;
+        get_block_cache(indirect2_block_id as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect2: &IndirectBlock| {
+                for entry in indirect2.iter().take(a1) {
+                    v.push(*entry);
+                    get_block_cache(*entry as usize, Arc::clone(block_device))
+                        .lock()
+                        .read(0, |indirect1: &IndirectBlock| {
+                            for entry in indirect1.iter() {
+                                v.push(*entry);
+                            }
+                        });
+                }
+                if b1 > 0 {
+                    v.push(indirect2[a1]);
+                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
+                        .lock()
+                        .read(0, |indirect1: &IndirectBlock| {
+                            for entry in indirect1.iter().take(b1) {
+                                v.push(*entry);
+                            }
+                        });
+                }
+            });
+    }
+    /// Read data from current disk inode
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        if start >= end {
+            return 0;
+        }
+        let mut start_block = start / BLOCK_SZ;
+        let mut read_size = 0usize;
+        loop {
+            // calculate end of current block
+            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            end_current_block = end_current_block.min(end);
+            // read and update read size
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .read(0, |data_block: &DataBlock| {
+                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                dst.copy_from_slice(src);
+            });
+            read_size += block_read_size;
+            // move to next block
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        read_size
+    }
+    /// Write data into current disk inode, size must be adjusted properly beforehand
+    pub fn write_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        assert!(start <= end);
+        let mut start_block = start / BLOCK_SZ;
+        let mut write_size = 0usize;
+        loop {
+            // calculate end of current block
+            let mut end_current_block = (start / BLOCK_SZ + 1) * BLOCK_SZ;
+            end_current_block = end_current_block.min(end);
+            // write and update write size
+            let block_write_size = end_current_block - start;
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                let src = &buf[write_size..write_size + block_write_size];
+                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
+                dst.copy_from_slice(src);
+            });
+            write_size += block_write_size;
+            // move to next block
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        write_size
+    }
+}
+/// A directory entry
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+/// Size of a directory entry
+pub const DIRENT_SZ: usize = 32;
+
+impl DirEntry {
+    /// Create an empty directory entry
+    pub fn empty() -> Self {
+        Self {
+            name: [0u8; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+    /// Create a directory entry from name and inode number
+    pub fn new(name: &str, inode_number: u32) -> Self {
+        let mut bytes = [0u8; NAME_LENGTH_LIMIT + 1];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Self {
+            name: bytes,
+            inode_number,
+        }
+    }
+    /// Serialize into bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, DIRENT_SZ) }
+    }
+    /// Serialize into mutable bytes
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SZ) }
+    }
+    /// Get name of the entry
+    pub fn name(&self) -> &str {
+        let len = (0usize..).find(|i| self.name[*i] == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+    /// Get inode number of the entry
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::MemBlockDevice;
+
+    /// Grow a `DiskInode` to `data_blocks` blocks and back down to zero,
+    /// checking that `blocks_num_needed` and the number of blocks `clear_size`
+    /// returns both agree with `total_blocks`, and that `total_blocks` fresh
+    /// blocks are enough for `increase_size` -- this is exactly
+    /// the accounting an earlier indirect3 bug got wrong.
+    fn grow_then_clear_round_trip(data_blocks: u32) {
+        let _guard = crate::tests::lock();
+        let new_size = data_blocks * BLOCK_SZ as u32;
+        let total = DiskInode::total_blocks(new_size);
+        let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(total as usize));
+        let new_blocks: Vec<u32> = (0..total).collect();
+
+        let mut inode: DiskInode = unsafe { core::mem::zeroed() };
+        inode.initialize(DiskInodeType::File, DEFAULT_FILE_MODE, 0, 0, 0);
+        assert_eq!(inode.blocks_num_needed(new_size), total);
+        inode.increase_size(new_size, new_blocks, &device);
+        assert_eq!(inode.data_blocks(), data_blocks);
+        assert!(inode.get_block_id(data_blocks - 1, &device) < total);
+
+        let freed = inode.clear_size(&device);
+        assert_eq!(freed.len(), total as usize);
+        assert_eq!(inode.size, 0);
+        assert_eq!(inode.indirect1, 0);
+        assert_eq!(inode.indirect2, 0);
+        assert_eq!(inode.indirect3, 0);
+    }
+
+    #[test]
+    fn direct_to_indirect1_boundary_round_trips() {
+        grow_then_clear_round_trip(DIRECT_BOUND as u32 + 2);
+    }
+
+    #[test]
+    fn indirect1_to_indirect2_boundary_round_trips() {
+        grow_then_clear_round_trip(INDIRECT1_BOUND as u32 + 2);
+    }
+
+    #[test]
+    fn indirect2_to_indirect3_boundary_round_trips() {
+        grow_then_clear_round_trip(INDIRECT2_BOUND as u32 + 3);
+    }
+}
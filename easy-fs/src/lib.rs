@@ -0,0 +1,22 @@
+#![cfg_attr(not(test), no_std)]
+//! An easy file system isolated from the kernel
+extern crate alloc;
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod efs;
+mod fsck;
+mod layout;
+#[cfg(test)]
+mod tests;
+mod vfs;
+
+/// Use a block size of 512 bytes
+pub const BLOCK_SZ: usize = 512;
+pub use bitmap::Bitmap;
+pub use block_cache::{block_cache_sync_all, get_block_cache};
+pub use block_dev::BlockDevice;
+pub use efs::{EasyFileSystem, FsStat};
+pub use fsck::{FsckError, FsckReport};
+pub use layout::*;
+pub use vfs::Inode;
@@ -0,0 +1,39 @@
+//! Test-only utilities shared by the `#[cfg(test)]` modules scattered across
+//! this crate.
+//!
+//! The global block cache (see `block_cache.rs`) is keyed by `block_id`
+//! alone, not by which `BlockDevice` it came from, so two tests running
+//! concurrently against overlapping block ids on separate in-memory devices
+//! could read back each other's cached blocks. [`lock`] serializes every test
+//! that touches the cache; acquire it as the first statement in any such
+//! test.
+use crate::{BlockDevice, BLOCK_SZ};
+use std::sync::{Mutex, MutexGuard};
+
+pub static SERIAL: Mutex<()> = Mutex::new(());
+
+pub fn lock() -> MutexGuard<'static, ()> {
+    SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An in-memory [`BlockDevice`] for tests, backed by a growable `Vec`
+pub struct MemBlockDevice {
+    blocks: Mutex<Vec<[u8; BLOCK_SZ]>>,
+}
+
+impl MemBlockDevice {
+    pub fn new(total_blocks: usize) -> Self {
+        Self {
+            blocks: Mutex::new(vec![[0u8; BLOCK_SZ]; total_blocks]),
+        }
+    }
+}
+
+impl BlockDevice for MemBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.blocks.lock().unwrap()[block_id]);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.blocks.lock().unwrap()[block_id].copy_from_slice(buf);
+    }
+}